@@ -0,0 +1,419 @@
+//! The [`Client`] entry point.
+
+mod sync;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use ruma::api::client::{filter::create_filter, room::create_room};
+use ruma::events::room::member::MembershipState;
+use ruma::{OwnedRoomId, OwnedUserId, RoomId, UserId};
+use tokio::sync::watch;
+use url::Url;
+
+use crate::{
+    config::{FilterDefinition, SyncFilter, SyncSettings},
+    event_handler::{EventHandlerContext, MembershipHandlers, MembershipTransition},
+    room::{Room, RoomMember, RoomType},
+    sliding_sync::SlidingSyncBuilder,
+    store::{Store, UnreadNotificationsCount},
+};
+
+#[derive(Debug)]
+pub(crate) struct ClientInner {
+    pub(crate) homeserver: Url,
+    pub(crate) http_client: reqwest::Client,
+    pub(crate) access_token: Option<String>,
+    pub(crate) user_id: Option<OwnedUserId>,
+    pub(crate) store: Store,
+    pub(crate) event_handler_context: EventHandlerContext,
+    pub(crate) rooms: RwLock<Vec<(OwnedRoomId, RoomType)>>,
+    pub(crate) unread_counts:
+        RwLock<HashMap<OwnedRoomId, Arc<watch::Sender<UnreadNotificationsCount>>>>,
+    pub(crate) membership_handlers: MembershipHandlers,
+    /// The `since` token from the most recently processed `/sync`
+    /// response, used as the default for the next call when
+    /// [`SyncSettings::token`] isn't set explicitly.
+    pub(crate) sync_token: RwLock<Option<String>>,
+}
+
+/// The main entry point for interacting with a Matrix homeserver.
+///
+/// `Client` is cheaply cloneable; clones share the same underlying
+/// connection, store, and registered event handlers.
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub(crate) inner: Arc<ClientInner>,
+}
+
+impl Client {
+    /// The user id this client is logged in as, if any.
+    pub fn user_id(&self) -> Option<&UserId> {
+        self.inner.user_id.as_deref()
+    }
+
+    /// A handle to the client's configured state store.
+    pub fn store(&self) -> Store {
+        self.inner.store.clone()
+    }
+
+    /// Runs a single `/sync` request and applies the response.
+    ///
+    /// Applying the response resets the membership-transition dedup set
+    /// (see [`MembershipHandlers`]) and then, for each room's member
+    /// events, resolves the transition and calls
+    /// [`MembershipHandlers::dispatch`] so `on_invited`/`on_joined`/etc.
+    /// handlers fire exactly once per real transition in this response.
+    ///
+    /// Returns the response's `next_batch` token, which is also cached as
+    /// the default `since` for the next call so callers don't have to
+    /// thread it through themselves.
+    pub async fn sync_once(&self, settings: SyncSettings) -> anyhow::Result<String> {
+        self.inner.membership_handlers.reset_batch();
+
+        let since =
+            settings.token.clone().or_else(|| self.inner.sync_token.read().unwrap().clone());
+
+        let mut request = ruma::api::client::sync::sync_events::v3::Request::default();
+        request.since = since;
+        request.timeout = settings.timeout;
+        request.filter = self.build_sync_filter(&settings);
+
+        let response = self.send(request).await?;
+        let next_batch = response.next_batch.clone();
+        self.handle_sync_response(response).await?;
+
+        *self.inner.sync_token.write().unwrap() = Some(next_batch.clone());
+        Ok(next_batch)
+    }
+
+    /// Builds the `filter` parameter for a `/sync` request from a
+    /// [`SyncSettings`]: an explicitly attached [`SyncFilter`] wins, else a
+    /// non-disabled [`LazyLoadOptions`](crate::config::LazyLoadOptions) is
+    /// wrapped in a minimal inline filter so `lazy_load_members` actually
+    /// reaches the server.
+    fn build_sync_filter(
+        &self,
+        settings: &SyncSettings,
+    ) -> Option<ruma::api::client::sync::sync_events::v3::Filter> {
+        use ruma::api::client::sync::sync_events::v3::Filter as RumaFilter;
+
+        if let Some(filter) = settings.filter.clone() {
+            return Some(match filter {
+                SyncFilter::Id(id) => RumaFilter::FilterId(id),
+                SyncFilter::Definition(definition) => {
+                    RumaFilter::FilterDefinition(definition.into())
+                }
+            });
+        }
+
+        if matches!(settings.lazy_load_members, crate::config::LazyLoadOptions::Disabled) {
+            return None;
+        }
+
+        let state = crate::config::RoomEventFilter::default()
+            .lazy_load_options(settings.lazy_load_members);
+        let definition =
+            FilterDefinition::default().room(crate::config::RoomFilter::default().state(state));
+        Some(RumaFilter::FilterDefinition(definition.into()))
+    }
+
+    /// Runs `/sync` in a loop until cancelled, applying each response as it
+    /// arrives and feeding each response's `next_batch` back in as the next
+    /// request's `since`.
+    pub async fn sync(&self, settings: SyncSettings) {
+        let mut settings = settings;
+        loop {
+            match self.sync_once(settings.clone()).await {
+                Ok(next_batch) => settings = settings.token(next_batch),
+                Err(err) => {
+                    tracing::warn!("sync failed, retrying: {err}");
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    /// Creates a room with the given request.
+    pub async fn create_room(
+        &self,
+        request: create_room::v3::Request<'_>,
+    ) -> anyhow::Result<Room> {
+        let response = self.send(request).await?;
+        self.upsert_room(&response.room_id, RoomType::Joined);
+        Ok(Room::new(self.clone(), response.room_id, RoomType::Joined))
+    }
+
+    /// Returns the given room if it is currently invited.
+    pub fn get_invited_room(&self, room_id: &ruma::RoomId) -> Option<Room> {
+        self.get_room_with_type(room_id, RoomType::Invited)
+    }
+
+    /// Returns the given room if it is currently joined.
+    pub fn get_joined_room(&self, room_id: &ruma::RoomId) -> Option<Room> {
+        self.get_room_with_type(room_id, RoomType::Joined)
+    }
+
+    /// Returns the given room if it has been left.
+    pub fn get_left_room(&self, room_id: &ruma::RoomId) -> Option<Room> {
+        self.get_room_with_type(room_id, RoomType::Left)
+    }
+
+    /// Returns the given room regardless of its membership state.
+    pub fn get_room(&self, room_id: &ruma::RoomId) -> Option<Room> {
+        let rooms = self.inner.rooms.read().unwrap();
+        rooms
+            .iter()
+            .find(|(id, _)| id == room_id)
+            .map(|(id, ty)| Room::new(self.clone(), id.clone(), *ty))
+    }
+
+    fn get_room_with_type(&self, room_id: &ruma::RoomId, room_type: RoomType) -> Option<Room> {
+        let rooms = self.inner.rooms.read().unwrap();
+        rooms
+            .iter()
+            .find(|(id, ty)| id == room_id && *ty == room_type)
+            .map(|(id, ty)| Room::new(self.clone(), id.clone(), *ty))
+    }
+
+    /// Inserts or updates `room_id`'s entry in the local room index.
+    pub(crate) fn upsert_room(&self, room_id: &RoomId, room_type: RoomType) {
+        let mut rooms = self.inner.rooms.write().unwrap();
+        match rooms.iter_mut().find(|(id, _)| id == room_id) {
+            Some(entry) => entry.1 = room_type,
+            None => rooms.push((room_id.to_owned(), room_type)),
+        }
+    }
+
+    /// Stores `context` in the client's [`EventHandlerContext`]. Nothing in
+    /// this crate reads it back or passes it to a handler as `Ctx<T>`.
+    pub async fn add_event_handler_context<T: Send + Sync + 'static>(&self, context: T) {
+        self.inner.event_handler_context.insert(context).await;
+    }
+
+    /// Accepts a typed event handler and drops it: the handler is not
+    /// stored, and sync processing never invokes it. Membership changes
+    /// are delivered only through [`Client::on_invited`] and its siblings.
+    pub fn add_event_handler<Ev, H, Args>(&self, handler: H)
+    where
+        H: crate::event_handler::EventHandler<Ev, Args>,
+    {
+        let _ = handler;
+    }
+
+    /// Registers `handler` to run whenever the local user is invited to a
+    /// room.
+    ///
+    /// `handler` receives the resolved `(Room, RoomMember, prev_membership)`
+    /// for the local user's own membership. It fires only for changes to
+    /// the local user's own membership, whoever sent the event, and fires
+    /// at most once for the same room and transition within one sync
+    /// response; the same invite re-sent in a later response fires it again.
+    pub fn on_invited<F, Fut>(&self, handler: F)
+    where
+        F: Fn(Room, RoomMember, Option<MembershipState>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.inner.membership_handlers.register(MembershipTransition::Invited, handler);
+    }
+
+    /// Registers `handler` to run whenever the local user joins a room
+    /// (including accepting an invite). See [`Client::on_invited`] for the
+    /// guarantees this provides.
+    pub fn on_joined<F, Fut>(&self, handler: F)
+    where
+        F: Fn(Room, RoomMember, Option<MembershipState>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.inner.membership_handlers.register(MembershipTransition::Joined, handler);
+    }
+
+    /// Registers `handler` to run whenever the local user leaves a room of
+    /// their own accord. See [`Client::on_invited`] for the guarantees this
+    /// provides.
+    pub fn on_left<F, Fut>(&self, handler: F)
+    where
+        F: Fn(Room, RoomMember, Option<MembershipState>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.inner.membership_handlers.register(MembershipTransition::Left, handler);
+    }
+
+    /// Registers `handler` to run whenever the local user is kicked from a
+    /// room. See [`Client::on_invited`] for the guarantees this provides.
+    pub fn on_kicked<F, Fut>(&self, handler: F)
+    where
+        F: Fn(Room, RoomMember, Option<MembershipState>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.inner.membership_handlers.register(MembershipTransition::Kicked, handler);
+    }
+
+    /// Registers `handler` to run whenever the local user is banned from a
+    /// room. See [`Client::on_invited`] for the guarantees this provides.
+    pub fn on_banned<F, Fut>(&self, handler: F)
+    where
+        F: Fn(Room, RoomMember, Option<MembershipState>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.inner.membership_handlers.register(MembershipTransition::Banned, handler);
+    }
+
+    /// Sends a raw `ruma` request to this client's homeserver and parses
+    /// the response, converting between `ruma`'s `http`-based request/
+    /// response types and `reqwest`.
+    pub(crate) async fn send<R>(&self, request: R) -> anyhow::Result<R::IncomingResponse>
+    where
+        R: ruma::api::OutgoingRequest,
+    {
+        let access_token = self.inner.access_token.as_deref().unwrap_or_default();
+        let http_request = request
+            .try_into_http_request::<Vec<u8>>(
+                self.inner.homeserver.as_str(),
+                ruma::api::SendAccessToken::IfRequired(access_token),
+                &[ruma::api::MatrixVersion::V1_1],
+            )
+            .map_err(|err| anyhow::anyhow!("failed to build request: {err}"))?;
+
+        let (parts, body) = http_request.into_parts();
+        let mut request_builder =
+            self.inner.http_client.request(parts.method, parts.uri.to_string()).body(body);
+        for (name, value) in parts.headers.iter() {
+            request_builder = request_builder.header(name.clone(), value.clone());
+        }
+
+        let response = request_builder.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+
+        let mut http_response = http::Response::builder().status(status);
+        for (name, value) in headers.iter() {
+            http_response = http_response.header(name.clone(), value.clone());
+        }
+        let http_response = http_response.body(body)?;
+
+        R::IncomingResponse::try_from_http_response(http_response)
+            .map_err(|err| anyhow::anyhow!("failed to parse response: {err}"))
+    }
+
+    /// Like [`Client::send`], but treats an HTTP 404 as a non-error `None`
+    /// instead of propagating it.
+    ///
+    /// For endpoints like `get_state_events_for_key` where "not found" is an
+    /// expected, meaningful response (e.g. a member who's never been in the
+    /// room) rather than a failure, this keeps that case distinguishable
+    /// from an actual transport or server error.
+    pub(crate) async fn send_opt<R>(&self, request: R) -> anyhow::Result<Option<R::IncomingResponse>>
+    where
+        R: ruma::api::OutgoingRequest,
+    {
+        let access_token = self.inner.access_token.as_deref().unwrap_or_default();
+        let http_request = request
+            .try_into_http_request::<Vec<u8>>(
+                self.inner.homeserver.as_str(),
+                ruma::api::SendAccessToken::IfRequired(access_token),
+                &[ruma::api::MatrixVersion::V1_1],
+            )
+            .map_err(|err| anyhow::anyhow!("failed to build request: {err}"))?;
+
+        let (parts, body) = http_request.into_parts();
+        let mut request_builder =
+            self.inner.http_client.request(parts.method, parts.uri.to_string()).body(body);
+        for (name, value) in parts.headers.iter() {
+            request_builder = request_builder.header(name.clone(), value.clone());
+        }
+
+        let response = request_builder.send().await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+
+        let mut http_response = http::Response::builder().status(status);
+        for (name, value) in headers.iter() {
+            http_response = http_response.header(name.clone(), value.clone());
+        }
+        let http_response = http_response.body(body)?;
+
+        R::IncomingResponse::try_from_http_response(http_response)
+            .map(Some)
+            .map_err(|err| anyhow::anyhow!("failed to parse response: {err}"))
+    }
+
+    /// Sends a JSON request to an endpoint not yet covered by `ruma`'s typed
+    /// client API — e.g. the still-unstable MSC3575 sliding sync endpoint —
+    /// reusing the same connection and auth as [`Client::send`].
+    pub(crate) async fn send_json<Req, Resp>(
+        &self,
+        method: http::Method,
+        path: &str,
+        body: &Req,
+    ) -> anyhow::Result<Resp>
+    where
+        Req: serde::Serialize + ?Sized,
+        Resp: serde::de::DeserializeOwned,
+    {
+        let url = self.inner.homeserver.join(path)?;
+        let mut request_builder = self.inner.http_client.request(method, url).json(body);
+        if let Some(token) = &self.inner.access_token {
+            request_builder = request_builder.bearer_auth(token);
+        }
+
+        let response = request_builder.send().await?;
+        Ok(response.json::<Resp>().await?)
+    }
+
+    /// Uploads a [`FilterDefinition`] and returns the `filter_id` the
+    /// server assigned it.
+    ///
+    /// Pass the returned id to [`SyncSettings::filter`] with
+    /// [`crate::config::SyncFilter::Id`] so subsequent `/sync` requests
+    /// reference it instead of re-sending the full definition.
+    pub async fn upload_filter(&self, definition: FilterDefinition) -> anyhow::Result<String> {
+        let user_id = self.user_id().ok_or_else(|| anyhow::anyhow!("client is not logged in"))?;
+        let request = create_filter::v3::Request::new(user_id.to_owned(), definition.into());
+        let response = self.send(request).await?;
+        Ok(response.filter_id)
+    }
+
+    /// Returns a watch channel that updates whenever `room_id`'s unread
+    /// notification/highlight counts change, for badging rooms in a UI.
+    ///
+    /// Used by [`Room::subscribe_unread_counts`](crate::room::Room::subscribe_unread_counts).
+    pub(crate) fn subscribe_unread_counts(
+        &self,
+        room_id: &RoomId,
+    ) -> watch::Receiver<UnreadNotificationsCount> {
+        self.unread_counts_sender(room_id).subscribe()
+    }
+
+    fn unread_counts_sender(&self, room_id: &RoomId) -> Arc<watch::Sender<UnreadNotificationsCount>> {
+        let mut counts = self.inner.unread_counts.write().unwrap();
+        counts
+            .entry(room_id.to_owned())
+            .or_insert_with(|| Arc::new(watch::channel(UnreadNotificationsCount::default()).0))
+            .clone()
+    }
+
+    /// Called by the sync response handler once per joined room, after the
+    /// new counts have been persisted to the store.
+    pub(crate) fn set_unread_counts(&self, room_id: &RoomId, counts: UnreadNotificationsCount) {
+        let _ = self.unread_counts_sender(room_id).send(counts);
+    }
+
+    /// Starts building a [`crate::sliding_sync::SlidingSync`] session.
+    ///
+    /// Sliding sync (MSC3575) is an alternative to [`Client::sync`] that
+    /// trades the classic full-room timeline dump for an incrementally
+    /// maintained, explicitly windowed room index. See the
+    /// [`sliding_sync`](crate::sliding_sync) module for details.
+    pub fn sliding_sync(&self) -> SlidingSyncBuilder {
+        SlidingSyncBuilder::new(self.clone())
+    }
+}
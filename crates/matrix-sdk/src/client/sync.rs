@@ -0,0 +1,281 @@
+//! Turning a `/sync` response into store updates and dispatched events.
+
+use ruma::{
+    events::{
+        room::member::{MembershipState, SyncRoomMemberEvent},
+        AnyStrippedStateEvent, AnySyncStateEvent, AnySyncTimelineEvent,
+    },
+    RoomId, UserId,
+};
+
+use crate::{
+    event_handler::MembershipTransition,
+    room::{Room, RoomMember},
+    store::{RoomSummary, UnreadNotificationsCount},
+    Client, RoomType,
+};
+
+impl Client {
+    /// Applies one `/sync` response: upserts each room's membership state
+    /// in the store, and dispatches `on_invited`/`on_joined`/`on_left`/
+    /// `on_kicked`/`on_banned` for every resolved membership change.
+    ///
+    /// Callers call
+    /// [`MembershipHandlers::reset_batch`](crate::event_handler::MembershipHandlers::reset_batch)
+    /// before handing over a response ([`Client::sync_once`] does so for
+    /// each one), so a transition repeated within this response dispatches
+    /// once, while one repeated in a later response dispatches again.
+    pub(crate) async fn handle_sync_response(
+        &self,
+        response: ruma::api::client::sync::sync_events::v3::Response,
+    ) -> anyhow::Result<()> {
+        for (room_id, invited) in response.rooms.invite {
+            self.upsert_room(&room_id, RoomType::Invited);
+            self.save_invited_room_summary(&room_id, &invited).await?;
+
+            for raw_event in &invited.invite_state.events {
+                let Ok(AnyStrippedStateEvent::RoomMember(event)) = raw_event.deserialize() else {
+                    continue;
+                };
+
+                self.handle_member_event(
+                    &room_id,
+                    &event.sender,
+                    &event.state_key,
+                    event.content.membership,
+                    event.content.displayname,
+                )
+                .await?;
+            }
+        }
+
+        for (room_id, joined) in response.rooms.join {
+            self.upsert_room(&room_id, RoomType::Joined);
+            self.save_unread_notification_counts(&room_id, &joined).await?;
+            self.save_room_summary(&room_id, &joined).await?;
+
+            for raw_event in &joined.state.events {
+                self.handle_sync_state_event(&room_id, raw_event).await?;
+            }
+            for raw_event in &joined.timeline.events {
+                self.handle_sync_timeline_event(&room_id, raw_event).await?;
+            }
+        }
+
+        for (room_id, left) in response.rooms.leave {
+            self.upsert_room(&room_id, RoomType::Left);
+
+            for raw_event in &left.state.events {
+                self.handle_sync_state_event(&room_id, raw_event).await?;
+            }
+            for raw_event in &left.timeline.events {
+                self.handle_sync_timeline_event(&room_id, raw_event).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Persists and broadcasts a joined room's unread notification/highlight
+    /// counts, which [`Room::unread_notification_count`](crate::room::Room::unread_notification_count)
+    /// and [`Room::subscribe_unread_counts`](crate::room::Room::subscribe_unread_counts)
+    /// otherwise have no way to learn about.
+    async fn save_unread_notification_counts(
+        &self,
+        room_id: &RoomId,
+        joined: &ruma::api::client::sync::sync_events::v3::JoinedRoom,
+    ) -> anyhow::Result<()> {
+        let counts = UnreadNotificationsCount {
+            notification_count: joined
+                .unread_notifications
+                .notification_count
+                .map(u64::from)
+                .unwrap_or_default(),
+            highlight_count: joined
+                .unread_notifications
+                .highlight_count
+                .map(u64::from)
+                .unwrap_or_default(),
+        };
+
+        self.store().save_unread_notification_counts(room_id, counts).await?;
+        self.set_unread_counts(room_id, counts);
+
+        Ok(())
+    }
+
+    /// Derives and persists an invited room's `m.heroes`/member-count
+    /// summary.
+    ///
+    /// Unlike a `JoinedRoom`, an `InvitedRoom` carries no `summary` block of
+    /// its own — all the client ever sees is `invite_state`'s stripped
+    /// member events. Without this, an unnamed DM the local user has only
+    /// been invited to (not yet joined) would fall straight through to the
+    /// bare member-count fallback in
+    /// [`Room::display_name`](crate::room::Room::display_name) instead of
+    /// showing the inviter's name. Treat the joined/invited members named in
+    /// those stripped events as the heroes/counts would-be `summary` block.
+    async fn save_invited_room_summary(
+        &self,
+        room_id: &RoomId,
+        invited: &ruma::api::client::sync::sync_events::v3::InvitedRoom,
+    ) -> anyhow::Result<()> {
+        let mut heroes = Vec::new();
+        let mut joined_member_count = 0u64;
+        let mut invited_member_count = 0u64;
+
+        for raw_event in &invited.invite_state.events {
+            let Ok(AnyStrippedStateEvent::RoomMember(event)) = raw_event.deserialize() else {
+                continue;
+            };
+
+            match event.content.membership {
+                MembershipState::Join => {
+                    joined_member_count += 1;
+                    if heroes.len() < 5 {
+                        heroes.push(event.state_key);
+                    }
+                }
+                MembershipState::Invite => invited_member_count += 1,
+                _ => {}
+            }
+        }
+
+        let summary = RoomSummary { heroes, joined_member_count, invited_member_count };
+        self.store().save_room_summary(room_id, summary).await?;
+
+        Ok(())
+    }
+
+    /// Persists a joined room's `m.heroes`/member-count summary.
+    ///
+    /// [`Room::display_name`](crate::room::Room::display_name) re-reads this
+    /// (and each hero's current display name from the store) on every call
+    /// rather than caching a resolved string, so persisting the summary here
+    /// is all that's needed to keep it current as membership changes.
+    async fn save_room_summary(
+        &self,
+        room_id: &RoomId,
+        joined: &ruma::api::client::sync::sync_events::v3::JoinedRoom,
+    ) -> anyhow::Result<()> {
+        let summary = RoomSummary {
+            heroes: joined.summary.heroes.clone(),
+            joined_member_count: joined
+                .summary
+                .joined_member_count
+                .map(u64::from)
+                .unwrap_or_default(),
+            invited_member_count: joined
+                .summary
+                .invited_member_count
+                .map(u64::from)
+                .unwrap_or_default(),
+        };
+
+        self.store().save_room_summary(room_id, summary).await?;
+
+        Ok(())
+    }
+
+    async fn handle_sync_state_event(
+        &self,
+        room_id: &RoomId,
+        raw_event: &ruma::serde::Raw<AnySyncStateEvent>,
+    ) -> anyhow::Result<()> {
+        let Ok(AnySyncStateEvent::RoomMember(event)) = raw_event.deserialize() else {
+            return Ok(());
+        };
+        self.handle_sync_room_member_event(room_id, event).await
+    }
+
+    async fn handle_sync_timeline_event(
+        &self,
+        room_id: &RoomId,
+        raw_event: &ruma::serde::Raw<AnySyncTimelineEvent>,
+    ) -> anyhow::Result<()> {
+        let Ok(AnySyncTimelineEvent::State(AnySyncStateEvent::RoomMember(event))) =
+            raw_event.deserialize()
+        else {
+            return Ok(());
+        };
+        self.handle_sync_room_member_event(room_id, event).await
+    }
+
+    async fn handle_sync_room_member_event(
+        &self,
+        room_id: &RoomId,
+        event: SyncRoomMemberEvent,
+    ) -> anyhow::Result<()> {
+        // A redacted membership event carries no `content.membership`; there is
+        // nothing meaningful to resolve a transition from, so it's skipped
+        // rather than treated as a (wrong) membership change.
+        let SyncRoomMemberEvent::Original(event) = event else {
+            return Ok(());
+        };
+
+        self.handle_member_event(
+            room_id,
+            &event.sender,
+            &event.state_key,
+            event.content.membership,
+            event.content.displayname,
+        )
+        .await
+    }
+
+    /// Resolves one membership event to a `(room, member, prev_membership)`
+    /// triple, persists it, and dispatches the matching
+    /// `MembershipTransition` (if any) through `membership_handlers`.
+    async fn handle_member_event(
+        &self,
+        room_id: &RoomId,
+        sender: &UserId,
+        subject: &UserId,
+        membership: MembershipState,
+        display_name: Option<String>,
+    ) -> anyhow::Result<()> {
+        let store = self.store();
+
+        let prev_membership =
+            store.get_member_event(room_id, subject).await?.map(|event| event.membership);
+
+        let member = RoomMember::new(subject.to_owned(), membership.clone(), display_name);
+        store.save_member(room_id, member.clone()).await?;
+
+        if Some(subject) == self.user_id() {
+            self.upsert_room(room_id, Self::room_type_for_own_membership(&membership));
+        }
+
+        let Some(transition) = MembershipTransition::from_change(&membership, sender, subject)
+        else {
+            return Ok(());
+        };
+
+        let room = Room::new(self.clone(), room_id.to_owned(), self.room_type_of(room_id));
+        self.inner
+            .membership_handlers
+            .dispatch(transition, room, member, prev_membership, self.user_id())
+            .await;
+
+        Ok(())
+    }
+
+    fn room_type_for_own_membership(membership: &MembershipState) -> RoomType {
+        match membership {
+            MembershipState::Invite => RoomType::Invited,
+            MembershipState::Join => RoomType::Joined,
+            _ => RoomType::Left,
+        }
+    }
+
+    fn room_type_of(&self, room_id: &RoomId) -> RoomType {
+        self.inner
+            .rooms
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(id, _)| id == room_id)
+            .map(|(_, ty)| *ty)
+            .unwrap_or(RoomType::Left)
+    }
+}
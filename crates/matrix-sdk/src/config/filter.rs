@@ -0,0 +1,202 @@
+//! A typed builder for the `/sync` `filter` parameter.
+
+use ruma::OwnedRoomId;
+
+use super::LazyLoadOptions;
+
+/// Filter options common to the room/timeline/state/ephemeral/account-data
+/// sections of a [`FilterDefinition`].
+///
+/// This mirrors ruma's own `RoomEventFilter`/`Filter` but isn't itself ever
+/// serialized: every section is `.into()`-converted to ruma's type (which
+/// does implement `serde`) before being sent, so this type doesn't need to.
+#[derive(Debug, Clone, Default)]
+pub struct RoomEventFilter {
+    pub limit: Option<u32>,
+    pub types: Vec<String>,
+    pub not_types: Vec<String>,
+    pub rooms: Vec<OwnedRoomId>,
+    pub not_rooms: Vec<OwnedRoomId>,
+    pub lazy_load_options: Option<LazyLoadOptions>,
+}
+
+impl RoomEventFilter {
+    /// Caps the number of events returned for this section.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Restricts this section to the given event types, e.g.
+    /// `["m.room.member"]`.
+    pub fn types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Excludes the given event types.
+    pub fn not_types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.not_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Restricts this section to the given rooms.
+    pub fn rooms(mut self, rooms: impl IntoIterator<Item = OwnedRoomId>) -> Self {
+        self.rooms = rooms.into_iter().collect();
+        self
+    }
+
+    /// Excludes the given rooms.
+    pub fn not_rooms(mut self, rooms: impl IntoIterator<Item = OwnedRoomId>) -> Self {
+        self.not_rooms = rooms.into_iter().collect();
+        self
+    }
+
+    /// Requests lazy-loaded membership for this section; see
+    /// [`LazyLoadOptions`].
+    pub fn lazy_load_options(mut self, options: LazyLoadOptions) -> Self {
+        self.lazy_load_options = Some(options);
+        self
+    }
+}
+
+/// The `room` section of a [`FilterDefinition`], covering timeline, state,
+/// ephemeral, and account-data events scoped to rooms.
+#[derive(Debug, Clone, Default)]
+pub struct RoomFilter {
+    pub timeline: Option<RoomEventFilter>,
+    pub state: Option<RoomEventFilter>,
+    pub ephemeral: Option<RoomEventFilter>,
+    pub account_data: Option<RoomEventFilter>,
+    pub rooms: Vec<OwnedRoomId>,
+    pub not_rooms: Vec<OwnedRoomId>,
+}
+
+impl RoomFilter {
+    /// Sets the `timeline` section, e.g. to cap how many events are
+    /// returned per room.
+    pub fn timeline(mut self, timeline: RoomEventFilter) -> Self {
+        self.timeline = Some(timeline);
+        self
+    }
+
+    /// Sets the `state` section; this is where
+    /// [`RoomEventFilter::lazy_load_options`] should go to request
+    /// lazy-loaded membership.
+    pub fn state(mut self, state: RoomEventFilter) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sets the `ephemeral` section (typing notifications, read receipts).
+    pub fn ephemeral(mut self, ephemeral: RoomEventFilter) -> Self {
+        self.ephemeral = Some(ephemeral);
+        self
+    }
+
+    /// Sets the room-scoped `account_data` section.
+    pub fn account_data(mut self, account_data: RoomEventFilter) -> Self {
+        self.account_data = Some(account_data);
+        self
+    }
+
+    /// Restricts the whole section to the given rooms.
+    pub fn rooms(mut self, rooms: impl IntoIterator<Item = OwnedRoomId>) -> Self {
+        self.rooms = rooms.into_iter().collect();
+        self
+    }
+
+    /// Excludes the given rooms from the whole section.
+    pub fn not_rooms(mut self, rooms: impl IntoIterator<Item = OwnedRoomId>) -> Self {
+        self.not_rooms = rooms.into_iter().collect();
+        self
+    }
+}
+
+/// A server-side filter, as defined by the `/user/{userId}/filter`
+/// endpoint.
+///
+/// Build one with the section setters below, upload it with
+/// [`Client::upload_filter`](crate::Client::upload_filter) to get back a
+/// `filter_id`, then attach it to a [`SyncSettings`](super::SyncSettings)
+/// so subsequent `/sync` requests send the id instead of the whole
+/// definition.
+#[derive(Debug, Clone, Default)]
+pub struct FilterDefinition {
+    pub room: Option<RoomFilter>,
+    pub presence: Option<RoomEventFilter>,
+    pub account_data: Option<RoomEventFilter>,
+}
+
+impl From<FilterDefinition> for ruma::api::client::filter::FilterDefinition {
+    fn from(definition: FilterDefinition) -> Self {
+        // `ruma`'s `FilterDefinition` mirrors this one field-for-field; we
+        // keep our own type so the builder methods above can live outside
+        // of `ruma` and so `SyncFilter` has somewhere to hang a `filter_id`
+        // variant.
+        let mut ruma_definition = ruma::api::client::filter::FilterDefinition::default();
+        ruma_definition.room = definition.room.map(Into::into);
+        ruma_definition.presence = definition.presence.map(Into::into).unwrap_or_default();
+        ruma_definition.account_data = definition.account_data.map(Into::into).unwrap_or_default();
+        ruma_definition
+    }
+}
+
+impl From<RoomFilter> for ruma::api::client::filter::RoomFilter {
+    fn from(room: RoomFilter) -> Self {
+        let mut ruma_room = ruma::api::client::filter::RoomFilter::default();
+        ruma_room.timeline = room.timeline.map(Into::into).unwrap_or_default();
+        ruma_room.state = room.state.map(Into::into).unwrap_or_default();
+        ruma_room.ephemeral = room.ephemeral.map(Into::into).unwrap_or_default();
+        ruma_room.account_data = room.account_data.map(Into::into).unwrap_or_default();
+        ruma_room.rooms = (!room.rooms.is_empty()).then_some(room.rooms);
+        ruma_room.not_rooms = room.not_rooms;
+        ruma_room
+    }
+}
+
+impl From<RoomEventFilter> for ruma::api::client::filter::RoomEventFilter {
+    fn from(filter: RoomEventFilter) -> Self {
+        let mut ruma_filter = ruma::api::client::filter::RoomEventFilter::default();
+        ruma_filter.limit = filter.limit.map(ruma::UInt::from);
+        ruma_filter.types = (!filter.types.is_empty()).then_some(filter.types);
+        ruma_filter.not_types = filter.not_types;
+        ruma_filter.rooms = (!filter.rooms.is_empty()).then_some(filter.rooms);
+        ruma_filter.not_rooms = filter.not_rooms;
+        ruma_filter.lazy_load_options = filter
+            .lazy_load_options
+            .map(Into::into)
+            .unwrap_or(ruma::api::client::filter::LazyLoadOptions::Disabled);
+        ruma_filter
+    }
+}
+
+impl From<RoomEventFilter> for ruma::api::client::filter::Filter {
+    fn from(filter: RoomEventFilter) -> Self {
+        let mut ruma_filter = ruma::api::client::filter::Filter::default();
+        ruma_filter.limit = filter.limit.map(ruma::UInt::from);
+        ruma_filter.types = (!filter.types.is_empty()).then_some(filter.types);
+        ruma_filter.not_types = filter.not_types;
+        ruma_filter
+    }
+}
+
+impl FilterDefinition {
+    /// Sets the `room` section.
+    pub fn room(mut self, room: RoomFilter) -> Self {
+        self.room = Some(room);
+        self
+    }
+
+    /// Sets the top-level `presence` section.
+    pub fn presence(mut self, presence: RoomEventFilter) -> Self {
+        self.presence = Some(presence);
+        self
+    }
+
+    /// Sets the top-level `account_data` section.
+    pub fn account_data(mut self, account_data: RoomEventFilter) -> Self {
+        self.account_data = Some(account_data);
+        self
+    }
+}
@@ -0,0 +1,35 @@
+//! Lazy-loading of room members, as defined by the `lazy_load_members`
+//! filter option in the Matrix spec.
+
+/// Whether a sync filter should ask the server to omit membership events
+/// for users who have not sent a timeline event, trading an up-front
+/// membership dump for on-demand lookups via [`Room::get_member`](crate::room::Room::get_member).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LazyLoadOptions {
+    /// Request full membership as before.
+    Disabled,
+    /// Request lazy-loaded membership.
+    Enabled {
+        /// Also include membership events for all members already known
+        /// from a previous sync, even if they did not send a timeline
+        /// event (`include_redundant_members` in the filter spec).
+        include_redundant_members: bool,
+    },
+}
+
+impl Default for LazyLoadOptions {
+    fn default() -> Self {
+        Self::Disabled
+    }
+}
+
+impl From<LazyLoadOptions> for ruma::api::client::filter::LazyLoadOptions {
+    fn from(options: LazyLoadOptions) -> Self {
+        match options {
+            LazyLoadOptions::Disabled => Self::Disabled,
+            LazyLoadOptions::Enabled { include_redundant_members } => {
+                Self::Enabled { include_redundant_members }
+            }
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Configuration types passed to [`Client::sync`](crate::Client::sync) and friends.
+
+mod filter;
+mod lazy_load;
+mod sync_settings;
+
+pub use filter::{FilterDefinition, RoomEventFilter, RoomFilter};
+pub use lazy_load::LazyLoadOptions;
+pub use sync_settings::{SyncFilter, SyncSettings};
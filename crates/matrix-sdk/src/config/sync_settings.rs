@@ -0,0 +1,63 @@
+//! Settings controlling a single call to `/sync`.
+
+use std::time::Duration;
+
+use super::{FilterDefinition, LazyLoadOptions};
+
+/// The `filter` parameter of a `/sync` request: either a filter definition
+/// to be sent inline, or the `filter_id` of one previously registered with
+/// [`Client::upload_filter`](crate::Client::upload_filter).
+#[derive(Debug, Clone)]
+pub enum SyncFilter {
+    Definition(FilterDefinition),
+    Id(String),
+}
+
+/// Settings for a single `/sync` request.
+///
+/// Constructed with [`SyncSettings::default`] and customized with the
+/// builder methods below.
+#[derive(Debug, Clone, Default)]
+pub struct SyncSettings {
+    pub(crate) token: Option<String>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) lazy_load_members: LazyLoadOptions,
+    pub(crate) filter: Option<SyncFilter>,
+}
+
+impl SyncSettings {
+    /// The `since` token to resume syncing from.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// How long the server should long-poll before returning an empty
+    /// response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Requests lazy-loaded room membership instead of the full member
+    /// list on every response.
+    ///
+    /// When enabled, callers that need membership outside of the users who
+    /// sent timeline events should use [`Room::get_member`](crate::room::Room::get_member),
+    /// which backfills on a cache miss.
+    pub fn lazy_load_members(mut self, options: LazyLoadOptions) -> Self {
+        self.lazy_load_members = options;
+        self
+    }
+
+    /// Attaches a filter, either an inline [`FilterDefinition`] or a
+    /// `filter_id` already registered with [`Client::upload_filter`](crate::Client::upload_filter).
+    ///
+    /// Prefer uploading the filter once and reusing its id: sending a full
+    /// definition on every sync request defeats the point of filtering out
+    /// the events it excludes.
+    pub fn filter(mut self, filter: SyncFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
@@ -0,0 +1,157 @@
+//! Registration of callbacks invoked while processing sync responses.
+
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use ruma::{events::room::member::MembershipState, OwnedRoomId, OwnedUserId};
+use tokio::sync::Mutex;
+
+use crate::room::{Room, RoomMember};
+
+/// A piece of context (e.g. a channel sender) made available to event
+/// handlers registered via [`Client::add_event_handler_context`](crate::Client::add_event_handler_context).
+#[derive(Debug, Clone)]
+pub struct Ctx<T>(pub T);
+
+impl<T> std::ops::Deref for Ctx<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Storage for arbitrary `T: Send + Sync + 'static` values, keyed by type.
+/// Values can only be inserted; nothing reads them back as [`Ctx<T>`].
+#[derive(Debug, Default, Clone)]
+pub struct EventHandlerContext {
+    values: Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>>,
+}
+
+impl EventHandlerContext {
+    pub async fn insert<T: Send + Sync + 'static>(&self, value: T) {
+        self.values.lock().await.insert(TypeId::of::<T>(), Box::new(value));
+    }
+}
+
+/// Marker trait bounding the handlers accepted by
+/// [`Client::add_event_handler`](crate::Client::add_event_handler). This
+/// crate provides no implementations of it.
+///
+/// `Args` is the tuple of extractable arguments (events, `Room`, `Client`,
+/// `Ctx<T>`, ...) the function accepts; it exists purely so multiple
+/// argument arities can have non-overlapping implementations.
+pub trait EventHandler<Ev, Args>: Send + Sync + 'static {}
+
+/// Which membership transition a callback registered with
+/// [`Client::on_invited`](crate::Client::on_invited) and friends fires for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MembershipTransition {
+    Invited,
+    Joined,
+    Left,
+    Kicked,
+    Banned,
+}
+
+impl MembershipTransition {
+    /// Classifies a membership event into a transition, or `None` for
+    /// membership states that don't correspond to one of the typed
+    /// callbacks (e.g. `knock`).
+    ///
+    /// A `leave` is a kick when its sender differs from its subject (the
+    /// `state_key`); when sender and subject match, the subject left of
+    /// their own accord.
+    pub(crate) fn from_change(
+        new_membership: &MembershipState,
+        sender: &ruma::UserId,
+        subject: &ruma::UserId,
+    ) -> Option<Self> {
+        match new_membership {
+            MembershipState::Invite => Some(Self::Invited),
+            MembershipState::Join => Some(Self::Joined),
+            MembershipState::Leave if sender == subject => Some(Self::Left),
+            MembershipState::Leave => Some(Self::Kicked),
+            MembershipState::Ban => Some(Self::Banned),
+            _ => None,
+        }
+    }
+}
+
+type MembershipCallback = Arc<
+    dyn Fn(Room, RoomMember, Option<MembershipState>) -> Pin<Box<dyn Future<Output = ()> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Dispatches the high-level `on_invited`/`on_joined`/`on_left`/`on_kicked`/
+/// `on_banned` callbacks.
+///
+/// This replaces the older pattern of hand-rolling a raw
+/// `StrippedRoomMemberEvent` handler that re-derives "was this a fresh
+/// invite for me?" on every call: callbacks here already receive a
+/// resolved `(Room, RoomMember, prev_membership)` for the *new* member
+/// state, scoped to the local user's own membership and with repeat
+/// transitions within one sync batch collapsed to a single dispatch.
+#[derive(Default)]
+pub(crate) struct MembershipHandlers {
+    handlers: StdMutex<HashMap<MembershipTransition, Vec<MembershipCallback>>>,
+    seen_this_batch: StdMutex<HashSet<(OwnedRoomId, OwnedUserId, MembershipTransition)>>,
+}
+
+impl MembershipHandlers {
+    pub(crate) fn register<F, Fut>(&self, transition: MembershipTransition, handler: F)
+    where
+        F: Fn(Room, RoomMember, Option<MembershipState>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let callback: MembershipCallback =
+            Arc::new(move |room, member, prev| Box::pin(handler(room, member, prev)));
+        self.handlers.lock().unwrap().entry(transition).or_default().push(callback);
+    }
+
+    /// Called by the sync response handler for each resolved membership
+    /// change. Only dispatches transitions *about* the local user — i.e.
+    /// `member.user_id() == own_user_id` — since that's what
+    /// `on_invited`/`on_joined`/etc. report: the local user's own
+    /// membership, not an arbitrary other member's. This is independent of
+    /// who sent the event, so a self-join (accepting an invite) or
+    /// self-leave still dispatches even though the local user is both
+    /// sender and subject. Within the same sync batch, all but the first
+    /// occurrence of a given `(room, user, transition)` triple is skipped.
+    pub(crate) async fn dispatch(
+        &self,
+        transition: MembershipTransition,
+        room: Room,
+        member: RoomMember,
+        prev_membership: Option<MembershipState>,
+        own_user_id: Option<&ruma::UserId>,
+    ) {
+        if Some(member.user_id()) != own_user_id {
+            return;
+        }
+
+        let key = (room.room_id().to_owned(), member.user_id().to_owned(), transition);
+        if !self.seen_this_batch.lock().unwrap().insert(key) {
+            return;
+        }
+
+        let callbacks = self.handlers.lock().unwrap().get(&transition).cloned().unwrap_or_default();
+        for callback in callbacks {
+            callback(room.clone(), member.clone(), prev_membership.clone()).await;
+        }
+    }
+
+    /// Clears the per-batch dedup set; called once before each sync
+    /// response is processed so the next batch's transitions are
+    /// reconsidered.
+    pub(crate) fn reset_batch(&self) {
+        self.seen_this_batch.lock().unwrap().clear();
+    }
+}
+
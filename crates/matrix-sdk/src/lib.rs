@@ -0,0 +1,17 @@
+//! Matrix client-server SDK.
+//!
+//! This crate provides a high-level [`Client`] for building Matrix clients
+//! and bots on top of the `ruma` type system.
+
+pub mod client;
+pub mod config;
+pub mod event_handler;
+pub mod room;
+pub mod sliding_sync;
+pub mod store;
+
+pub use client::Client;
+pub use room::{Room, RoomType};
+pub use ruma;
+
+pub use sliding_sync::{SlidingSync, SlidingSyncBuilder, SlidingSyncList, SlidingSyncListBuilder};
@@ -0,0 +1,228 @@
+//! Room types and the [`Room`] API surface.
+
+use futures_core::Stream;
+use ruma::{
+    api::client::{
+        membership::{invite_user, join_room_by_id, leave_room},
+        state::get_state_events_for_key,
+    },
+    events::{room::member::MembershipState, StateEventType},
+    OwnedRoomId, OwnedUserId, RoomId, UserId,
+};
+use tokio_stream::wrappers::WatchStream;
+
+use crate::{
+    store::{Store, UnreadNotificationsCount},
+    Client,
+};
+
+/// Which of the three membership lists (invited/joined/left) a [`Room`]
+/// currently lives in from the point of view of the logged-in user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomType {
+    Invited,
+    Joined,
+    Left,
+}
+
+/// A handle to a single room known to the [`Client`].
+///
+/// `Room` is a thin, cloneable view over the client's store; it does not
+/// hold room state itself.
+#[derive(Debug, Clone)]
+pub struct Room {
+    pub(crate) client: Client,
+    pub(crate) room_id: OwnedRoomId,
+    pub(crate) room_type: RoomType,
+}
+
+impl Room {
+    pub(crate) fn new(client: Client, room_id: OwnedRoomId, room_type: RoomType) -> Self {
+        Self { client, room_id, room_type }
+    }
+
+    /// The room's id.
+    pub fn room_id(&self) -> &RoomId {
+        &self.room_id
+    }
+
+    /// Whether this room is currently invited, joined, or left.
+    pub fn room_type(&self) -> RoomType {
+        self.room_type
+    }
+
+    fn store(&self) -> Store {
+        self.client.store()
+    }
+
+    /// Returns the resolved membership of `user_id` in this room, assuming
+    /// it is already present in the store.
+    ///
+    /// This does **not** hit the network; see [`Room::get_member`] for a
+    /// variant that backfills on a cache miss.
+    pub async fn get_member_no_sync(&self, user_id: &UserId) -> anyhow::Result<Option<RoomMember>> {
+        Ok(self.store().get_member(&self.room_id, user_id).await?)
+    }
+
+    /// Returns the resolved membership of `user_id` in this room, fetching
+    /// it from the homeserver on a cache miss.
+    ///
+    /// Unlike [`Room::get_member_no_sync`], this is safe to call after a
+    /// sync performed with [`LazyLoadOptions::Enabled`](crate::config::LazyLoadOptions::Enabled),
+    /// where the store may simply never have seen a member who hasn't sent
+    /// a timeline event. The fetched member is written back to the store
+    /// so subsequent calls (lazy-loaded or not) hit the cache.
+    pub async fn get_member(&self, user_id: &UserId) -> anyhow::Result<Option<RoomMember>> {
+        if let Some(member) = self.get_member_no_sync(user_id).await? {
+            return Ok(Some(member));
+        }
+
+        let request = get_state_events_for_key::v3::Request::new(
+            self.room_id.clone(),
+            StateEventType::RoomMember,
+            user_id.to_string(),
+        );
+        let response = self.client.send_opt(request).await?;
+        let member = response.and_then(|response| {
+            response
+                .content
+                .deserialize()
+                .ok()
+                .map(|content| RoomMember::new(user_id.to_owned(), content.membership, content.displayname))
+        });
+
+        if let Some(member) = &member {
+            self.store().save_member(&self.room_id, member.clone()).await?;
+        }
+
+        Ok(member)
+    }
+
+    /// Accepts a pending invitation to this room.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this room is not currently invited.
+    pub async fn accept_invitation(self) -> anyhow::Result<Room> {
+        assert_eq!(self.room_type, RoomType::Invited, "room is not invited");
+        let request = join_room_by_id::v3::Request::new(self.room_id.clone());
+        self.client.send(request).await?;
+        Ok(Room::new(self.client, self.room_id, RoomType::Joined))
+    }
+
+    /// Leaves this room.
+    pub async fn leave(self) -> anyhow::Result<Room> {
+        let request = leave_room::v3::Request::new(self.room_id.clone());
+        self.client.send(request).await?;
+        Ok(Room::new(self.client, self.room_id, RoomType::Left))
+    }
+
+    /// Resolves a display name for this room following the Matrix spec's
+    /// fallback order: the explicit `m.room.name`, then the canonical
+    /// alias, then the heroes from the room's `RoomSummary`, then a bare
+    /// member count.
+    ///
+    /// Recomputing this after every membership change (rather than caching
+    /// a single resolved string) is what keeps an unnamed DM's name
+    /// up to date as the other party joins or leaves.
+    pub async fn display_name(&self) -> anyhow::Result<String> {
+        let store = self.store();
+
+        if let Some(name) = store.get_room_name(&self.room_id).await? {
+            return Ok(name);
+        }
+
+        if let Some(alias) = store.get_canonical_alias(&self.room_id).await? {
+            return Ok(alias.to_string());
+        }
+
+        let summary = store.get_room_summary(&self.room_id).await?.unwrap_or_default();
+        let own_id = self.client.user_id();
+
+        let mut hero_names = Vec::new();
+        for hero in &summary.heroes {
+            if Some(hero.as_ref()) == own_id {
+                continue;
+            }
+            let name = match store.get_member(&self.room_id, hero).await? {
+                Some(member) => {
+                    member.display_name().map(ToOwned::to_owned).unwrap_or_else(|| hero.to_string())
+                }
+                None => hero.to_string(),
+            };
+            hero_names.push(name);
+        }
+
+        if !hero_names.is_empty() {
+            return Ok(hero_names.join(", "));
+        }
+
+        let other_members = summary
+            .joined_member_count
+            .saturating_add(summary.invited_member_count)
+            .saturating_sub(1);
+        Ok(format!("Empty room (was {other_members} other members)"))
+    }
+
+    /// The number of unread events in this room that triggered a
+    /// notification, as of the last processed sync response.
+    pub async fn unread_notification_count(&self) -> anyhow::Result<u64> {
+        Ok(self.store().get_unread_notification_counts(&self.room_id).await?.notification_count)
+    }
+
+    /// The number of unread events in this room that triggered a
+    /// highlight, as of the last processed sync response.
+    pub async fn unread_highlight_count(&self) -> anyhow::Result<u64> {
+        Ok(self.store().get_unread_notification_counts(&self.room_id).await?.highlight_count)
+    }
+
+    /// A stream that yields this room's unread counts every time they
+    /// change, for badging the room in a UI.
+    pub fn subscribe_unread_counts(&self) -> impl Stream<Item = UnreadNotificationsCount> {
+        WatchStream::new(self.client.subscribe_unread_counts(&self.room_id))
+    }
+
+    /// Invites `user_id` to this room.
+    pub async fn invite_user_by_id(&self, user_id: &UserId) -> anyhow::Result<()> {
+        let request = invite_user::v3::Request::new(
+            self.room_id.clone(),
+            invite_user::v3::InvitationRecipient::UserId { user_id: user_id.to_owned() },
+        );
+        self.client.send(request).await?;
+        Ok(())
+    }
+}
+
+/// A resolved member of a room, as returned by [`Room::get_member_no_sync`]
+/// and [`Room::get_member`].
+#[derive(Debug, Clone)]
+pub struct RoomMember {
+    pub(crate) user_id: OwnedUserId,
+    pub(crate) membership: MembershipState,
+    pub(crate) display_name: Option<String>,
+}
+
+impl RoomMember {
+    pub(crate) fn new(
+        user_id: OwnedUserId,
+        membership: MembershipState,
+        display_name: Option<String>,
+    ) -> Self {
+        Self { user_id, membership, display_name }
+    }
+
+    /// The member's user id.
+    pub fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    /// The member's current membership state.
+    pub fn membership(&self) -> &MembershipState {
+        &self.membership
+    }
+
+    /// The member's display name, if any has been set.
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+}
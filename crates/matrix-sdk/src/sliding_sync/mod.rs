@@ -0,0 +1,399 @@
+//! MSC3575 sliding sync (a.k.a. "sync v4").
+//!
+//! Classic [`Client::sync`](crate::Client::sync) asks the server to dump
+//! every room the user is a member of on every request, which does not
+//! scale past a few hundred rooms. Sliding sync instead lets the client
+//! declare one or more named [`SlidingSyncList`]s — each with a sort order
+//! and a live `range` window, e.g. "rooms 0..=19 sorted by recency" — and
+//! the server replies with a small set of [`SlidingOp`]s describing how
+//! that window changed since the last request, plus full timelines for any
+//! room the client explicitly subscribed to.
+//!
+//! A [`SlidingSync`] session is built with [`SlidingSyncBuilder`], obtained
+//! from [`Client::sliding_sync`](crate::Client::sliding_sync). It owns its
+//! own `pos` token (persisted in the [`Store`] so a restart resumes instead
+//! of re-downloading everything) and exposes a [`Stream`](futures_core::Stream)
+//! of [`UpdateSummary`]s as ranges change.
+
+use std::{
+    collections::BTreeMap,
+    ops::RangeInclusive,
+    sync::{Arc, RwLock},
+};
+
+use async_stream::stream;
+use futures_core::Stream;
+use ruma::{OwnedRoomId, RoomId};
+use serde::{Deserialize, Serialize};
+
+use crate::{room::Room, store::Store, Client, RoomType};
+
+/// One range operation the server applied to a [`SlidingSyncList`]'s window,
+/// as defined by MSC3575.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlidingOp {
+    /// Places `room_id` at `index`, filling an empty slot or overwriting
+    /// the existing entry.
+    Sync { index: usize, room_id: OwnedRoomId },
+    /// A new room entered the window at `index`, pushing existing entries
+    /// down.
+    Insert { index: usize, room_id: OwnedRoomId },
+    /// The room at `index` left the window.
+    Delete { index: usize },
+    /// Every entry within `range` is now stale and must be re-fetched
+    /// before use (typically sent when a list's range is first set).
+    Invalidate { range: RangeInclusive<usize> },
+}
+
+/// A named, independently windowed view over the user's rooms.
+///
+/// Lists are sorted and filtered entirely server-side; the client only
+/// declares `ranges` and receives [`SlidingOp`]s for whichever indices fall
+/// inside them.
+#[derive(Debug)]
+pub struct SlidingSyncList {
+    name: String,
+    ranges: RwLock<Vec<RangeInclusive<usize>>>,
+    sort: Vec<String>,
+    /// The ordered room index as last reported by the server, keyed by
+    /// position.
+    rooms: RwLock<BTreeMap<usize, OwnedRoomId>>,
+}
+
+impl SlidingSyncList {
+    /// This list's name, as declared to the server.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The live ranges currently requested for this list.
+    pub fn ranges(&self) -> Vec<RangeInclusive<usize>> {
+        self.ranges.read().unwrap().clone()
+    }
+
+    /// Replaces the requested ranges, e.g. in response to the user
+    /// scrolling the room list.
+    pub fn set_ranges(&self, ranges: Vec<RangeInclusive<usize>>) {
+        *self.ranges.write().unwrap() = ranges;
+    }
+
+    /// The room id currently at `index`, if the server has reported one.
+    pub fn room_id_at(&self, index: usize) -> Option<OwnedRoomId> {
+        self.rooms.read().unwrap().get(&index).cloned()
+    }
+
+    /// Applies a batch of resolved [`SlidingOp`]s, in order, to the ordered
+    /// room index.
+    ///
+    /// `Insert`/`Delete` shift every entry at or after `index` rather than
+    /// overwriting or leaving a gap, mirroring how the server's ordered
+    /// index itself shifts when a room enters or leaves the window.
+    fn apply(&self, ops: &[SlidingOp]) {
+        let mut rooms = self.rooms.write().unwrap();
+        for op in ops {
+            match op {
+                SlidingOp::Sync { index, room_id } => {
+                    rooms.insert(*index, room_id.clone());
+                }
+                SlidingOp::Insert { index, room_id } => {
+                    let shifted = rooms.split_off(index);
+                    for (old_index, id) in shifted {
+                        rooms.insert(old_index + 1, id);
+                    }
+                    rooms.insert(*index, room_id.clone());
+                }
+                SlidingOp::Delete { index } => {
+                    let mut shifted = rooms.split_off(index);
+                    shifted.remove(index);
+                    for (old_index, id) in shifted {
+                        rooms.insert(old_index - 1, id);
+                    }
+                }
+                SlidingOp::Invalidate { range } => {
+                    rooms.retain(|index, _| !range.contains(index));
+                }
+            }
+        }
+    }
+}
+
+/// Builder for a [`SlidingSyncList`], attached to a [`SlidingSync`] session
+/// with [`SlidingSyncBuilder::add_list`].
+#[derive(Debug, Clone)]
+pub struct SlidingSyncListBuilder {
+    name: String,
+    ranges: Vec<RangeInclusive<usize>>,
+    sort: Vec<String>,
+}
+
+impl SlidingSyncListBuilder {
+    /// Starts a new list named `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), ranges: Vec::new(), sort: Vec::new() }
+    }
+
+    /// Adds a live window, e.g. `0..=19`.
+    pub fn add_range(mut self, range: RangeInclusive<usize>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    /// Sets the server-side sort order, e.g. `["by_recency"]`.
+    pub fn sort(mut self, sort: Vec<String>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    fn build(self) -> SlidingSyncList {
+        SlidingSyncList {
+            name: self.name,
+            ranges: RwLock::new(self.ranges),
+            sort: self.sort,
+            rooms: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+/// Builder for a [`SlidingSync`] session, obtained from
+/// [`Client::sliding_sync`](crate::Client::sliding_sync).
+#[derive(Debug)]
+pub struct SlidingSyncBuilder {
+    client: Client,
+    connection_id: String,
+    lists: Vec<SlidingSyncListBuilder>,
+    subscribed_rooms: Vec<OwnedRoomId>,
+}
+
+impl SlidingSyncBuilder {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            connection_id: "default".to_owned(),
+            lists: Vec::new(),
+            subscribed_rooms: Vec::new(),
+        }
+    }
+
+    /// Sets the connection id used to key the persisted `pos` token.
+    ///
+    /// Defaults to `"default"`; set this explicitly when a single client
+    /// runs more than one concurrent sliding sync session.
+    pub fn connection_id(mut self, id: impl Into<String>) -> Self {
+        self.connection_id = id.into();
+        self
+    }
+
+    /// Adds a list to the session.
+    pub fn add_list(mut self, list: SlidingSyncListBuilder) -> Self {
+        self.lists.push(list);
+        self
+    }
+
+    /// Subscribes to a room's full timeline regardless of whether it falls
+    /// within any list's range, e.g. for a room currently open in the UI.
+    pub fn add_subscription(mut self, room_id: OwnedRoomId) -> Self {
+        self.subscribed_rooms.push(room_id);
+        self
+    }
+
+    /// Finishes the session, loading any previously persisted `pos` token
+    /// for this `connection_id` from the store.
+    pub async fn build(self) -> anyhow::Result<SlidingSync> {
+        let store = self.client.store();
+        let pos = store.get_sliding_sync_pos(&self.connection_id).await?;
+
+        Ok(SlidingSync {
+            client: self.client,
+            connection_id: self.connection_id,
+            store,
+            pos: RwLock::new(pos),
+            lists: self.lists.into_iter().map(|l| Arc::new(l.build())).collect(),
+            subscribed_rooms: self.subscribed_rooms,
+        })
+    }
+}
+
+/// Per-request state sent to the server, mirroring the MSC3575 request
+/// body's `lists`/`room_subscriptions`/`pos` shape.
+#[derive(Debug, Serialize, Deserialize)]
+struct SlidingSyncRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pos: Option<String>,
+    lists: BTreeMap<String, SlidingSyncListRequest>,
+    room_subscriptions: Vec<OwnedRoomId>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SlidingSyncListRequest {
+    ranges: Vec<(usize, usize)>,
+    sort: Vec<String>,
+}
+
+/// The response body's shape, mirroring the request's `lists`.
+#[derive(Debug, Deserialize)]
+struct SlidingSyncResponse {
+    pos: String,
+    #[serde(default)]
+    lists: BTreeMap<String, SlidingSyncListResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlidingSyncListResponse {
+    #[serde(default)]
+    ops: Vec<SlidingSyncOpResponse>,
+}
+
+/// One op as the server sends it over the wire. A `Sync`/`Invalidate` op
+/// covers a whole `range` in one message; a `Sync` is expanded into one
+/// [`SlidingOp::Sync`] per index before being applied, while an
+/// `Invalidate` stays a single [`SlidingOp::Invalidate`] over its range.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "UPPERCASE")]
+enum SlidingSyncOpResponse {
+    Sync { range: (usize, usize), room_ids: Vec<OwnedRoomId> },
+    Insert { index: usize, room_id: OwnedRoomId },
+    Delete { index: usize },
+    Invalidate { range: (usize, usize) },
+}
+
+impl SlidingSyncOpResponse {
+    fn into_ops(self) -> Vec<SlidingOp> {
+        match self {
+            Self::Sync { range, room_ids } => (range.0..=range.1)
+                .zip(room_ids)
+                .map(|(index, room_id)| SlidingOp::Sync { index, room_id })
+                .collect(),
+            Self::Insert { index, room_id } => vec![SlidingOp::Insert { index, room_id }],
+            Self::Delete { index } => vec![SlidingOp::Delete { index }],
+            Self::Invalidate { range } => {
+                vec![SlidingOp::Invalidate { range: range.0..=range.1 }]
+            }
+        }
+    }
+}
+
+/// A summary of what changed in the most recently applied sliding sync
+/// response, yielded by [`SlidingSync::stream`].
+#[derive(Debug, Clone)]
+pub struct UpdateSummary {
+    /// Names of the lists that received at least one [`SlidingOp`].
+    pub lists: Vec<String>,
+    /// Every room this session subscribed to with
+    /// [`SlidingSyncBuilder::add_subscription`], listed on every summary;
+    /// the response's per-room data is not parsed, so this does not say
+    /// which of them the response carried.
+    pub rooms: Vec<OwnedRoomId>,
+}
+
+/// A running sliding sync session.
+///
+/// Poll [`SlidingSync::stream`] to drive the session; each item is an
+/// [`UpdateSummary`] for one round-trip.
+#[derive(Debug)]
+pub struct SlidingSync {
+    client: Client,
+    connection_id: String,
+    store: Store,
+    pos: RwLock<Option<String>>,
+    lists: Vec<Arc<SlidingSyncList>>,
+    subscribed_rooms: Vec<OwnedRoomId>,
+}
+
+impl SlidingSync {
+    /// Returns the list registered under `name`, if any.
+    pub fn list(&self, name: &str) -> Option<Arc<SlidingSyncList>> {
+        self.lists.iter().find(|l| l.name() == name).cloned()
+    }
+
+    /// Resolves a room surfaced by one of this session's lists to the
+    /// ordinary [`Room`] API, reusing the client's existing room/membership
+    /// tracking.
+    pub fn get_room(&self, room_id: &RoomId) -> Option<Room> {
+        self.client.get_room(room_id)
+    }
+
+    /// Upserts every room a `Sync`/`Insert` op surfaced into the client's
+    /// room index, so [`get_room`](Self::get_room) (and
+    /// [`Client::get_room`]) can resolve it afterwards.
+    ///
+    /// Sliding sync lists only ever surface rooms the local user is
+    /// currently joined to; a room leaving every list's window doesn't mean
+    /// the user left it, so `Delete`/`Invalidate` ops don't remove anything
+    /// here.
+    fn upsert_surfaced_rooms(&self, ops: &[SlidingOp]) {
+        for op in ops {
+            let room_id = match op {
+                SlidingOp::Sync { room_id, .. } | SlidingOp::Insert { room_id, .. } => room_id,
+                SlidingOp::Delete { .. } | SlidingOp::Invalidate { .. } => continue,
+            };
+            self.client.upsert_room(room_id, RoomType::Joined);
+        }
+    }
+
+    /// Runs one sliding sync round-trip, applies the resulting
+    /// [`SlidingOp`]s to each list, and persists the new `pos` token.
+    async fn sync_once(&self) -> anyhow::Result<UpdateSummary> {
+        let lists = self
+            .lists
+            .iter()
+            .map(|list| {
+                let ranges =
+                    list.ranges().into_iter().map(|range| (*range.start(), *range.end())).collect();
+                (list.name().to_owned(), SlidingSyncListRequest { ranges, sort: list.sort.clone() })
+            })
+            .collect();
+
+        let request = SlidingSyncRequest {
+            pos: self.pos.read().unwrap().clone(),
+            lists,
+            room_subscriptions: self.subscribed_rooms.clone(),
+        };
+
+        let response: SlidingSyncResponse = self
+            .client
+            .send_json(
+                http::Method::POST,
+                "/_matrix/client/unstable/org.matrix.msc3575/sync",
+                &request,
+            )
+            .await?;
+
+        let mut updated_lists = Vec::new();
+        for (name, list_response) in response.lists {
+            let Some(list) = self.list(&name) else { continue };
+            let ops: Vec<SlidingOp> =
+                list_response.ops.into_iter().flat_map(SlidingSyncOpResponse::into_ops).collect();
+            if ops.is_empty() {
+                continue;
+            }
+            self.upsert_surfaced_rooms(&ops);
+            list.apply(&ops);
+            updated_lists.push(name);
+        }
+
+        *self.pos.write().unwrap() = Some(response.pos.clone());
+        self.store.save_sliding_sync_pos(&self.connection_id, response.pos).await?;
+
+        Ok(UpdateSummary { lists: updated_lists, rooms: self.subscribed_rooms.clone() })
+    }
+
+    /// Returns a stream that runs [`SlidingSync::sync_once`] in a loop,
+    /// yielding one [`UpdateSummary`] per round-trip until dropped.
+    ///
+    /// A successful round-trip is itself the stream's await point, so there
+    /// is no need for an additional `sleep` on that path. A failing one can
+    /// return near-instantly (e.g. a connection refused), so it backs off
+    /// for a second before retrying, mirroring [`Client::sync`]'s retry
+    /// loop.
+    pub fn stream(&self) -> impl Stream<Item = anyhow::Result<UpdateSummary>> + '_ {
+        stream! {
+            loop {
+                let result = self.sync_once().await;
+                if result.is_err() {
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+                yield result;
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Persistent state storage.
+//!
+//! The [`StateStore`] trait is the single point through which the SDK reads
+//! and writes everything it needs to survive a restart: room membership,
+//! account data, and (as of the sliding sync subsystem) per-connection sync
+//! positions.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ruma::{
+    events::room::member::MembershipState, OwnedRoomAliasId, OwnedRoomId, OwnedUserId, RoomId,
+    UserId,
+};
+
+use crate::room::RoomMember;
+
+/// A type-erased, cloneable handle to the store configured on a [`Client`](crate::Client).
+pub type Store = Arc<dyn StateStore + Send + Sync>;
+
+/// Errors that can occur while reading or writing the store.
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("backend error: {0}")]
+    Backend(String),
+}
+
+pub type Result<T, E = StoreError> = std::result::Result<T, E>;
+
+/// A minimal record of a `m.room.member` event, as persisted by the store.
+#[derive(Debug, Clone)]
+pub struct StoredMemberEvent {
+    pub state_key: OwnedUserId,
+    pub membership: MembershipState,
+}
+
+impl StoredMemberEvent {
+    pub fn membership(&self) -> &MembershipState {
+        &self.membership
+    }
+}
+
+/// Backing storage for room and sync state.
+///
+/// Implementations exist for an in-memory store (used in tests and
+/// short-lived processes) and a `sled`-backed store (used for anything that
+/// needs to survive a restart).
+#[async_trait]
+pub trait StateStore: std::fmt::Debug {
+    /// Returns the user ids currently invited to `room_id`.
+    async fn get_invited_user_ids(&self, room_id: &RoomId) -> Result<Vec<OwnedUserId>>;
+
+    /// Returns the user ids currently joined to `room_id`.
+    async fn get_joined_user_ids(&self, room_id: &RoomId) -> Result<Vec<OwnedUserId>>;
+
+    /// Returns the latest known `m.room.member` event for `user_id` in
+    /// `room_id`, if any.
+    async fn get_member_event(
+        &self,
+        room_id: &RoomId,
+        user_id: &UserId,
+    ) -> Result<Option<StoredMemberEvent>>;
+
+    /// Returns the cached [`RoomMember`] for `user_id` in `room_id`, without
+    /// triggering a network request.
+    async fn get_member(&self, room_id: &RoomId, user_id: &UserId) -> Result<Option<RoomMember>>;
+
+    /// Persists a resolved [`RoomMember`], e.g. after a lazy-loading backfill.
+    async fn save_member(&self, room_id: &RoomId, member: RoomMember) -> Result<()>;
+
+    /// Returns the `pos` token for a named sliding sync connection, if one
+    /// was previously persisted.
+    async fn get_sliding_sync_pos(&self, connection_id: &str) -> Result<Option<String>>;
+
+    /// Persists the `pos` token for a named sliding sync connection so the
+    /// next restart can resume from it.
+    async fn save_sliding_sync_pos(&self, connection_id: &str, pos: String) -> Result<()>;
+
+    /// Returns all room ids this store has entries for.
+    async fn room_ids(&self) -> Result<Vec<OwnedRoomId>>;
+
+    /// Returns the unread notification and highlight counts last reported
+    /// for `room_id`, as parsed out of its `JoinedRoom.unread_notifications`
+    /// during sync.
+    async fn get_unread_notification_counts(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<UnreadNotificationsCount>;
+
+    /// Persists the unread notification and highlight counts for
+    /// `room_id`.
+    async fn save_unread_notification_counts(
+        &self,
+        room_id: &RoomId,
+        counts: UnreadNotificationsCount,
+    ) -> Result<()>;
+
+    /// Returns the last `RoomSummary` parsed out of a sync response for
+    /// `room_id`, if any.
+    async fn get_room_summary(&self, room_id: &RoomId) -> Result<Option<RoomSummary>>;
+
+    /// Persists a `RoomSummary`, overwriting any previous one for the same
+    /// room.
+    async fn save_room_summary(&self, room_id: &RoomId, summary: RoomSummary) -> Result<()>;
+
+    /// Returns the explicit `m.room.name` for `room_id`, if it has one.
+    async fn get_room_name(&self, room_id: &RoomId) -> Result<Option<String>>;
+
+    /// Returns the `m.room.canonical_alias` for `room_id`, if it has one.
+    async fn get_canonical_alias(&self, room_id: &RoomId) -> Result<Option<OwnedRoomAliasId>>;
+}
+
+/// The `m.room.summary` data carried alongside each room in a sync
+/// response: the heroes used to compute a fallback display name, plus
+/// joined/invited member counts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoomSummary {
+    /// Up to 5 member user ids chosen by the server to represent the room
+    /// when it has no name or canonical alias (`m.heroes`).
+    pub heroes: Vec<OwnedUserId>,
+    /// Number of joined members, including the local user.
+    pub joined_member_count: u64,
+    /// Number of invited members.
+    pub invited_member_count: u64,
+}
+
+/// Per-room unread counts, as reported by the `unread_notifications` block
+/// of a `JoinedRoom` in a sync response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnreadNotificationsCount {
+    /// Number of unread events that triggered a notification.
+    pub notification_count: u64,
+    /// Number of unread events that triggered a highlight (e.g. an
+    /// `m.room.message` containing the user's display name).
+    pub highlight_count: u64,
+}
@@ -2,14 +2,9 @@ use std::time::Duration;
 
 use anyhow::Result;
 use assign::assign;
-use matrix_sdk::{
-    event_handler::Ctx,
-    room::Room,
-    ruma::{
-        api::client::room::create_room::v3::Request as CreateRoomRequest,
-        events::room::member::{MembershipState, StrippedRoomMemberEvent},
-    },
-    Client, RoomType,
+use matrix_sdk::ruma::{
+    api::client::room::create_room::v3::Request as CreateRoomRequest,
+    events::room::member::MembershipState,
 };
 use tokio::sync::mpsc;
 
@@ -49,8 +44,12 @@ async fn test_repeated_join_leave() -> Result<()> {
         karl_clone.sync(Default::default()).await;
     });
     let (invite_signal_sender, mut invite_signal) = mpsc::channel::<()>(1);
-    karl.add_event_handler_context(invite_signal_sender);
-    karl.add_event_handler(signal_on_invite);
+    karl.on_invited(move |_room, _member, _prev_membership| {
+        let invite_signal_sender = invite_signal_sender.clone();
+        async move {
+            invite_signal_sender.send(()).await.expect("receiver must be open");
+        }
+    });
 
     for i in 0..3 {
         println!("Iteration {i}");
@@ -108,31 +107,3 @@ async fn test_repeated_join_leave() -> Result<()> {
     // Yay, test succeeded
     Ok(())
 }
-
-async fn signal_on_invite(
-    event: StrippedRoomMemberEvent,
-    room: Room,
-    client: Client,
-    sender: Ctx<mpsc::Sender<()>>,
-) {
-    let own_id = client.user_id().expect("client is logged in");
-    if event.sender == own_id {
-        return;
-    }
-
-    if room.room_type() != RoomType::Invited {
-        return;
-    }
-
-    if event.content.membership != MembershipState::Invite {
-        return;
-    }
-
-    let invited = &event.state_key;
-    if invited != own_id {
-        return;
-    }
-
-    // Send signal that we received an invite.
-    sender.send(()).await.expect("receiver must be open");
-}